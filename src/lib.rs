@@ -8,10 +8,16 @@
 //!
 //! # Limitations
 //!
-//! This module only parses dumps containing only one revision of each page. This is what you get from the page `Special:Export` when enabling the option “Include only the current revision, not the full history”, as well as what you get from the Wikimedia dumps with file names ending with `-pages-articles.xml.bz2`.
+//! This module parses both dumps containing only the current revision of each page (what you get from the page `Special:Export` when enabling the option “Include only the current revision, not the full history”, as well as what you get from the Wikimedia dumps with file names ending with `-pages-articles.xml.bz2`) and dumps containing the full edit history of each page (`-pages-meta-history.xml.bz2`). Every revision of a page is available through `Page::revisions`.
 //!
 //! This module ignores the `siteinfo` element, every child element of the `page` element except `ns`, `revision` and `title`, and every element inside the `revision` element except `format`, `model` and `text`.
 //!
+//! By default `parse` returns every page in the dump. Use `ParserBuilder` to only receive pages in (or outside of) particular namespaces, to skip pages whose most recent revision isn't plain wikitext, or to make the parser skip over malformed pages instead of stopping the whole iteration.
+//!
+//! With the `serde` feature enabled, `Page`, `Revision` and `Contributor` derive `Serialize` and `Deserialize`, for re-emitting parsed pages (for example as JSON lines, or cached with bincode) without hand-writing the mapping. This feature is off by default, so the minimal dependency footprint of a default build is unaffected.
+//!
+//! With the `plain_text` feature enabled, `ParserBuilder::extract_plain_text` can be used to have each revision's `Revision::plain_text` filled in with a rough, stripped-down rendering of `Revision::text` suitable for building a corpus of article prose, with templates, emphasis markup, comments and `ref`/table markup removed and links replaced by their display text. This is not a substitute for a real wiki text parser; see the Caution above.
+//!
 //! Until there is a real use case that justifies going beyond these limitations, they will remain in order to avoid premature design driven by imagined requirements.
 //!
 //! # Examples
@@ -31,20 +37,23 @@
 //!                 eprintln!("Error: {}", error);
 //!                 break;
 //!             }
-//!             Ok(page) => if page.namespace == 0 && match &page.format {
-//!                 None => false,
-//!                 Some(format) => format == "text/x-wiki"
-//!             } && match &page.model {
-//!                 None => false,
-//!                 Some(model) => model == "wikitext"
-//!             } {
-//!                 println!(
-//!                     "The page {title:?} is an ordinary article with byte length {length}.",
-//!                     title = page.title,
-//!                     length = page.text.len()
-//!                 );
-//!             } else {
-//!                 println!("The page {:?} has something special to it.", page.title);
+//!             Ok(page) => match page.revision() {
+//!                 None => println!("The page {:?} has no revisions.", page.title),
+//!                 Some(revision) => if page.namespace == 0 && match &revision.format {
+//!                     None => false,
+//!                     Some(format) => format == "text/x-wiki"
+//!                 } && match &revision.model {
+//!                     None => false,
+//!                     Some(model) => model == "wikitext"
+//!                 } {
+//!                     println!(
+//!                         "The page {title:?} is an ordinary article with byte length {length}.",
+//!                         title = page.title,
+//!                         length = revision.text.len()
+//!                     );
+//!                 } else {
+//!                     println!("The page {:?} has something special to it.", page.title);
+//!                 }
 //!             }
 //!         }
 //!     }
@@ -55,6 +64,12 @@
 
 extern crate xml;
 
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde_derive;
+
 use std::io::Read;
 use xml::{common::{Position, TextPosition}, reader::{EventReader, XmlEvent}};
 
@@ -66,22 +81,52 @@ pub enum Error {
     /// Indicates the position in the stream.
     Format(TextPosition),
 
-    /// The source contains a feature not supported by the parser.
-    ///
-    /// In particular, this means a `page` element contains more than one `revision` element.
-    NotSupported(TextPosition),
-
     /// Error from the XML reader.
     XmlReader(xml::reader::Error)
 }
 
-/// Parsed page.
+/// The editor credited with a revision.
 ///
-/// Parsed from the `page` element.
+/// Parsed from the `contributor` element in a `revision` element. Anonymous edits have `username`
+/// and `id` absent and `ip` present; registered edits have `username` and `id` present and `ip`
+/// absent.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct Contributor {
+    /// The id of the registered user, if the edit was made by one.
+    ///
+    /// Parsed from the text content of the `id` element in the `contributor` element.
+    pub id: Option<u32>,
+
+    /// The IP address the edit was made from, if the edit was anonymous.
+    ///
+    /// Parsed from the text content of the `ip` element in the `contributor` element.
+    pub ip: Option<String>,
+
+    /// The name of the registered user, if the edit was made by one.
+    ///
+    /// Parsed from the text content of the `username` element in the `contributor` element.
+    pub username: Option<String>
+}
+
+/// A single revision of a page.
 ///
-/// Although the `format` and `model` elements are defined as mandatory in the [schema](https://www.mediawiki.org/xml/export-0.10.xsd), previous versions of the schema don't contain them. Therefore the corresponding fields can be `None`.
+/// Parsed from a `revision` element. A `page` element contains one `revision` element for dumps
+/// of only the current revision, or one `revision` element per historical revision for full
+/// history dumps.
 #[derive(Debug)]
-pub struct Page {
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct Revision {
+    /// The comment left with the revision if any.
+    ///
+    /// Parsed from the text content of the `comment` element in the `revision` element. `None` if the element is not present.
+    pub comment: Option<String>,
+
+    /// The contributor credited with the revision if any.
+    ///
+    /// Parsed from the `contributor` element in the `revision` element. `None` if the element is not present.
+    pub contributor: Option<Contributor>,
+
     /// The format of the revision if any.
     ///
     /// Parsed from the text content of the `format` element in the `revision` element. `None` if the element is not present.
@@ -89,6 +134,11 @@ pub struct Page {
     /// For ordinary articles the format is `text/x-wiki`.
     pub format: Option<String>,
 
+    /// The id of the revision if any.
+    ///
+    /// Parsed from the text content of the `id` element in the `revision` element. `None` if the element is not present.
+    pub id: Option<u64>,
+
     /// The model of the revision if any.
     ///
     /// Parsed from the text content of the `model` element in the `revision` element. `None` if the element is not present.
@@ -96,6 +146,41 @@ pub struct Page {
     /// For ordinary articles the model is `wikitext`.
     pub model: Option<String>,
 
+    /// A rough, stripped-down rendering of `text` with wiki markup removed, meant to approximate
+    /// the prose of the article.
+    ///
+    /// Only present if the parser was built with `ParserBuilder::extract_plain_text(true)`; `None`
+    /// otherwise. Requires the `plain_text` feature.
+    #[cfg(feature = "plain_text")]
+    pub plain_text: Option<String>,
+
+    /// The sha1 hash of the revision text if any.
+    ///
+    /// Parsed from the text content of the `sha1` element in the `revision` element. `None` if the element is not present.
+    pub sha1: Option<String>,
+
+    /// The text of the revision.
+    ///
+    /// Parsed from the text content of the `text` element in the `revision` element.
+    pub text: String,
+
+    /// The timestamp of the revision if any.
+    ///
+    /// Parsed from the text content of the `timestamp` element in the `revision` element, without further parsing. `None` if the element is not present.
+    pub timestamp: Option<String>
+}
+
+/// Parsed page.
+///
+/// Parsed from the `page` element.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct Page {
+    /// The id of the page if any.
+    ///
+    /// Parsed from the text content of the `id` element in the `page` element. `None` if the element is not present.
+    pub id: Option<u64>,
+
     /// The namespace of the page.
     ///
     /// Parsed from the text content of the `ns` element in the `page` element.
@@ -103,10 +188,11 @@ pub struct Page {
     /// For ordinary articles the namespace is 0.
     pub namespace: u32,
 
-    /// The text of the revision.
+    /// The revisions of the page, in the order they appear in the dump.
     ///
-    /// Parsed from the text content of the `text` element in the `revision` element.
-    pub text: String,
+    /// Dumps of only the current revision have exactly one element here. Full history dumps have
+    /// one element per revision, oldest first; `Page::revision` returns the most recent one.
+    pub revisions: Vec<Revision>,
 
     /// The title of the page.
     ///
@@ -114,17 +200,136 @@ pub struct Page {
     pub title: String
 }
 
+impl Page {
+    /// The most recent revision of the page, if any.
+    ///
+    /// For full history dumps this is the last element of `revisions`. For dumps of only the
+    /// current revision this is the only revision. `Page` values produced by `Parser` always have
+    /// at least one revision, but `Page` is a public, deserializable struct, so a `Page`
+    /// constructed or deserialized by other means may have an empty `revisions`; `None` is
+    /// returned in that case rather than panicking.
+    pub fn revision(&self) -> Option<&Revision> {
+        self.revisions.last()
+    }
+}
+
+/// Configures a `Parser` before parsing begins.
+///
+/// Created with `ParserBuilder::new`, or its `Default` implementation. A builder with no settings applied behaves exactly like `parse`.
+#[derive(Default)]
+pub struct ParserBuilder {
+    #[cfg(feature = "plain_text")]
+    extract_plain_text: bool,
+    lenient: bool,
+    namespace_filter: Option<NamespaceFilter>,
+    only_wikitext: bool
+}
+
+enum NamespaceFilter {
+    Allow(Vec<u32>),
+    Deny(Vec<u32>)
+}
+
+impl NamespaceFilter {
+    fn allows(&self, namespace: u32) -> bool {
+        match self {
+            NamespaceFilter::Allow(namespaces) => namespaces.contains(&namespace),
+            NamespaceFilter::Deny(namespaces) => !namespaces.contains(&namespace)
+        }
+    }
+}
+
+impl ParserBuilder {
+    /// Creates a builder with no filtering, equivalent to `parse`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only yields pages in one of the given namespaces.
+    ///
+    /// Pages outside the allowed namespaces are skipped as soon as their `ns` element is read, without parsing the rest of the page. Overrides any previous call to `allow_namespaces` or `deny_namespaces`.
+    pub fn allow_namespaces(mut self, namespaces: Vec<u32>) -> Self {
+        self.namespace_filter = Some(NamespaceFilter::Allow(namespaces));
+        self
+    }
+
+    /// Skips pages in any of the given namespaces.
+    ///
+    /// Denied pages are skipped as soon as their `ns` element is read, without parsing the rest of the page. Overrides any previous call to `allow_namespaces` or `deny_namespaces`.
+    pub fn deny_namespaces(mut self, namespaces: Vec<u32>) -> Self {
+        self.namespace_filter = Some(NamespaceFilter::Deny(namespaces));
+        self
+    }
+
+    /// Only yields pages whose most recent revision has format `text/x-wiki` and model `wikitext`.
+    ///
+    /// Pages missing either element, or with a different format or model, are skipped.
+    pub fn only_wikitext(mut self, value: bool) -> Self {
+        self.only_wikitext = value;
+        self
+    }
+
+    /// Fills in `Revision::plain_text` for every parsed revision.
+    ///
+    /// The conversion from wiki text to plain text is a rough approximation meant for building a
+    /// corpus of article prose; see the module's Caution section. Requires the `plain_text`
+    /// feature.
+    #[cfg(feature = "plain_text")]
+    pub fn extract_plain_text(mut self, value: bool) -> Self {
+        self.extract_plain_text = value;
+        self
+    }
+
+    /// Makes the parser resilient to malformed pages.
+    ///
+    /// Normally an error is fatal to the whole iteration: once `next` returns an `Err`, the underlying stream position is no longer aligned with a `page` boundary, so further calls are unreliable. With this enabled, a recoverable error (an `Error::Format`, i.e. a `page` whose content didn't match expectations) is still yielded as an `Err`, but the parser then resynchronizes to the end of the offending `page` element and keeps going. Use `Parser::skipped_pages` to find out how many pages were lost this way. An `Error::XmlReader` (a problem reading the underlying stream itself) is always fatal regardless of this setting, since there's no reason to expect the stream to recover.
+    pub fn lenient(mut self, value: bool) -> Self {
+        self.lenient = value;
+        self
+    }
+
+    /// Builds a `Parser` for a stream using this configuration.
+    pub fn build<R: Read>(self, source: R) -> Parser<R> {
+        Parser {
+            depth: 0,
+            event_reader: EventReader::new(source),
+            #[cfg(feature = "plain_text")]
+            extract_plain_text: self.extract_plain_text,
+            lenient: self.lenient,
+            namespace_filter: self.namespace_filter,
+            only_wikitext: self.only_wikitext,
+            skipped_pages: 0,
+            started: false
+        }
+    }
+}
+
 /// Parser working as an iterator over pages.
 pub struct Parser<R: Read> {
+    depth: u32,
     event_reader: ::EventReader<R>,
+    #[cfg(feature = "plain_text")]
+    extract_plain_text: bool,
+    lenient: bool,
+    namespace_filter: Option<NamespaceFilter>,
+    only_wikitext: bool,
+    skipped_pages: usize,
     started: bool
 }
 
+impl<R: Read> Parser<R> {
+    /// The number of pages skipped so far because they could not be parsed.
+    ///
+    /// Always 0 unless the parser was built with `ParserBuilder::lenient(true)`.
+    pub fn skipped_pages(&self) -> usize {
+        self.skipped_pages
+    }
+}
+
 impl std::fmt::Display for Error {
     fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             Error::Format(position) => write!(formatter, "Invalid format at position {}", position),
-            Error::NotSupported(position) => write!(formatter, "The element at position {} is not supported", position),
             Error::XmlReader(error) => error.fmt(formatter)
         }
     }
@@ -147,6 +352,17 @@ impl<R: Read> Iterator for Parser<R> {
     }
 }
 
+fn is_wikitext(revision: &Revision) -> bool {
+    format_model_is_wikitext(&revision.format, &revision.model)
+}
+
+fn format_model_is_wikitext(format: &Option<String>, model: &Option<String>) -> bool {
+    match (format, model) {
+        (Some(format), Some(model)) => format == "text/x-wiki" && model == "wikitext",
+        _ => false
+    }
+}
+
 fn match_namespace(name: &xml::name::OwnedName) -> bool {
     match &name.namespace {
         None => false,
@@ -157,7 +373,7 @@ fn match_namespace(name: &xml::name::OwnedName) -> bool {
 fn next(parser: &mut Parser<impl Read>) -> Result<Option<Page>, Error> {
     if !parser.started {
         loop {
-            if let XmlEvent::StartElement { name, .. } = parser.event_reader.next()? {
+            if let XmlEvent::StartElement { name, .. } = read_event(parser)? {
                 if match_namespace(&name) && name.local_name == "mediawiki" {
                     break;
                 }
@@ -167,88 +383,214 @@ fn next(parser: &mut Parser<impl Read>) -> Result<Option<Page>, Error> {
         parser.started = true;
     }
     loop {
-        match parser.event_reader.next()? {
+        match read_event(parser)? {
             XmlEvent::EndElement { .. } => return Ok(None),
-            XmlEvent::StartElement { name, .. } => if match &name.namespace {
-                None => false,
-                Some(namespace) => namespace == "http://www.mediawiki.org/xml/export-0.10/"
-            } && name.local_name == "page" {
-                let mut format = None;
-                let mut model = None;
-                let mut namespace = None;
-                let mut text = None;
-                let mut title = None;
-                loop {
-                    match parser.event_reader.next()? {
-                        XmlEvent::EndElement { .. } => return match (namespace, text, title) {
-                            (Some(namespace), Some(text), Some(title)) => Ok(Some(Page { format, model, namespace, text, title })),
-                            _ => Err(Error::Format(parser.event_reader.position()))
-                        },
-                        XmlEvent::StartElement { name, .. } => {
-                            if match &name.namespace {
+            XmlEvent::StartElement { name, .. } => if match_namespace(&name) && name.local_name == "page" {
+                let page_depth = parser.depth;
+                match parse_page(parser) {
+                    Ok(None) => continue,
+                    Ok(page) => return Ok(page),
+                    Err(error) => {
+                        if parser.lenient && !matches!(error, Error::XmlReader(_)) {
+                            resync(parser, page_depth);
+                            parser.skipped_pages += 1;
+                        }
+                        return Err(error);
+                    }
+                }
+            } else {
+                skip_element(parser);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Parses the content of a `page` element, assuming its start tag has already been consumed.
+///
+/// Returns `Ok(None)` if the page was filtered out by the parser's configuration; the page has
+/// still been fully consumed from the stream in that case.
+fn parse_page(parser: &mut Parser<impl Read>) -> Result<Option<Page>, Error> {
+    let mut id: Option<u64> = None;
+    let mut namespace = None;
+    let mut revisions = Vec::new();
+    let mut title = None;
+    loop {
+        match read_event(parser)? {
+            XmlEvent::EndElement { .. } => return match (namespace, title) {
+                (Some(namespace), Some(title)) if !revisions.is_empty() => {
+                    if parser.only_wikitext && !revisions.last().is_some_and(is_wikitext) {
+                        return Ok(None);
+                    }
+                    Ok(Some(Page { id, namespace, revisions, title }))
+                }
+                _ => Err(Error::Format(parser.event_reader.position()))
+            },
+            XmlEvent::StartElement { name, .. } => {
+                if match_namespace(&name) {
+                    match &name.local_name as _ {
+                        "id" => {
+                            id = Some(parse_uint(parser, &id)?);
+                            continue;
+                        }
+                        "ns" => {
+                            let value = parse_uint(parser, &namespace)?;
+                            if match &parser.namespace_filter {
                                 None => false,
-                                Some(namespace) => namespace == "http://www.mediawiki.org/xml/export-0.10/"
+                                Some(filter) => !filter.allows(value)
                             } {
-                                match &name.local_name as _ {
-                                    "ns" => match parse_text(&mut parser.event_reader, &namespace)?.parse() {
-                                        Err(_) => return Err(Error::Format(parser.event_reader.position())),
-                                        Ok(value) => {
-                                            namespace = Some(value);
-                                            continue;
-                                        }
-                                    }
-                                    "revision" => {
-                                        if text.is_some() {
-                                            return Err(Error::NotSupported(parser.event_reader.position()));
-                                        }
-                                        loop {
-                                            match parser.event_reader.next()? {
-                                                XmlEvent::EndElement { .. } => match text {
-                                                    None => return Err(Error::Format(parser.event_reader.position())),
-                                                    Some(_) => break
-                                                }
-                                                XmlEvent::StartElement { name, .. } => {
-                                                    if match &name.namespace {
-                                                        None => false,
-                                                        Some(namespace) => namespace == "http://www.mediawiki.org/xml/export-0.10/"
-                                                    } {
-                                                        match &name.local_name as _ {
-                                                            "format" => {
-                                                                format = Some(parse_text(&mut parser.event_reader, &mut format)?);
-                                                                continue;
-                                                            }
-                                                            "model" => {
-                                                                model = Some(parse_text(&mut parser.event_reader, &mut model)?);
-                                                                continue;
-                                                            }
-                                                            "text" => {
-                                                                text = Some(parse_text(&mut parser.event_reader, &mut text)?);
-                                                                continue;
-                                                            }
-                                                            _ => {}
-                                                        }
-                                                    }
-                                                    skip_element(&mut parser.event_reader);
-                                                }
-                                                _ => {}
-                                            }
-                                        }
-                                        continue;
-                                    }
-                                    "title" => {
-                                        title = Some(parse_text(&mut parser.event_reader, &title)?);
-                                        continue;
-                                    }
-                                    _ => {}
-                                }
+                                skip_element(parser);
+                                return Ok(None);
                             }
-                            skip_element(&mut parser.event_reader);
+                            namespace = Some(value);
+                            continue;
+                        }
+                        "revision" => {
+                            revisions.push(parse_revision(parser, parser.only_wikitext)?);
+                            continue;
+                        }
+                        "title" => {
+                            title = Some(parse_text(parser, &title)?);
+                            continue;
                         }
                         _ => {}
                     }
                 }
-            } else {
-                skip_element(&mut parser.event_reader);
+                skip_element(parser);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Parses the content of a `revision` element, assuming its start tag has already been consumed.
+///
+/// `only_wikitext` mirrors `ParserBuilder::only_wikitext`. In real dumps `format` and `model`
+/// always precede `text`, so if they're already known by the time `text` is reached and they mark
+/// this revision as not plain wikitext, its `text` is skipped without being allocated rather than
+/// parsed and then possibly thrown away by the caller's own `only_wikitext` check.
+fn parse_revision(parser: &mut Parser<impl Read>, only_wikitext: bool) -> Result<Revision, Error> {
+    let mut comment = None;
+    let mut contributor = None;
+    let mut format = None;
+    let mut id: Option<u64> = None;
+    let mut model = None;
+    let mut sha1 = None;
+    let mut text: Option<String> = None;
+    let mut timestamp = None;
+    loop {
+        match read_event(parser)? {
+            XmlEvent::EndElement { .. } => return match text {
+                None => Err(Error::Format(parser.event_reader.position())),
+                Some(text) => {
+                    #[cfg(feature = "plain_text")]
+                    let plain_text = if parser.extract_plain_text {
+                        Some(strip_wikitext(text.as_str()))
+                    } else {
+                        None
+                    };
+                    Ok(Revision {
+                        comment,
+                        contributor,
+                        format,
+                        id,
+                        model,
+                        #[cfg(feature = "plain_text")]
+                        plain_text,
+                        sha1,
+                        text,
+                        timestamp
+                    })
+                }
+            },
+            XmlEvent::StartElement { name, .. } => {
+                if match_namespace(&name) {
+                    match &name.local_name as _ {
+                        "comment" => {
+                            comment = Some(parse_text(parser, &comment)?);
+                            continue;
+                        }
+                        "contributor" => {
+                            if contributor.is_some() {
+                                return Err(Error::Format(parser.event_reader.position()));
+                            }
+                            contributor = Some(parse_contributor(parser)?);
+                            continue;
+                        }
+                        "format" => {
+                            format = Some(parse_text(parser, &format)?);
+                            continue;
+                        }
+                        "id" => {
+                            id = Some(parse_uint(parser, &id)?);
+                            continue;
+                        }
+                        "model" => {
+                            model = Some(parse_text(parser, &model)?);
+                            continue;
+                        }
+                        "sha1" => {
+                            sha1 = Some(parse_text(parser, &sha1)?);
+                            continue;
+                        }
+                        "text" => {
+                            if text.is_some() {
+                                return Err(Error::Format(parser.event_reader.position()));
+                            }
+                            // Only trust a verdict once both fields have actually arrived; if
+                            // either is still `None` here it may just not have been parsed yet
+                            // (rather than genuinely absent), so this only ever skips
+                            // allocating text we already know would be discarded.
+                            let known_not_wikitext = format.is_some() && model.is_some()
+                                && !format_model_is_wikitext(&format, &model);
+                            text = Some(if only_wikitext && known_not_wikitext {
+                                skip_element(parser);
+                                String::new()
+                            } else {
+                                parse_text(parser, &text)?
+                            });
+                            continue;
+                        }
+                        "timestamp" => {
+                            timestamp = Some(parse_text(parser, &timestamp)?);
+                            continue;
+                        }
+                        _ => {}
+                    }
+                }
+                skip_element(parser);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn parse_contributor(parser: &mut Parser<impl Read>) -> Result<Contributor, Error> {
+    let mut id = None;
+    let mut ip = None;
+    let mut username = None;
+    loop {
+        match read_event(parser)? {
+            XmlEvent::EndElement { .. } => return Ok(Contributor { id, ip, username }),
+            XmlEvent::StartElement { name, .. } => {
+                if match_namespace(&name) {
+                    match &name.local_name as _ {
+                        "id" => {
+                            id = Some(parse_uint(parser, &id)?);
+                            continue;
+                        }
+                        "ip" => {
+                            ip = Some(parse_text(parser, &ip)?);
+                            continue;
+                        }
+                        "username" => {
+                            username = Some(parse_text(parser, &username)?);
+                            continue;
+                        }
+                        _ => {}
+                    }
+                }
+                skip_element(parser);
             }
             _ => {}
         }
@@ -257,34 +599,72 @@ fn next(parser: &mut Parser<impl Read>) -> Result<Option<Page>, Error> {
 
 /// Creates a parser for a stream.
 ///
-/// The stream is parsed as an XML dump exported from Mediawiki. The parser is an iterator over the pages in the dump.
+/// The stream is parsed as an XML dump exported from Mediawiki. The parser is an iterator over the pages in the dump, with no filtering applied. Both dumps of only the current revision and full history dumps are supported; see `Page::revisions`. Use `ParserBuilder` to filter by namespace or to skip non-wikitext pages.
 pub fn parse<R: Read>(source: R) -> Parser<R> {
-    Parser {
-        event_reader: EventReader::new(source),
-        started: false
+    ParserBuilder::new().build(source)
+}
+
+/// Reads the next event, keeping `parser.depth` in sync with the nesting of elements seen so far.
+fn read_event(parser: &mut Parser<impl Read>) -> Result<XmlEvent, Error> {
+    let event = parser.event_reader.next()?;
+    match event {
+        XmlEvent::StartElement { .. } => parser.depth += 1,
+        XmlEvent::EndElement { .. } => parser.depth -= 1,
+        _ => {}
+    }
+    Ok(event)
+}
+
+/// Consumes events until the stream is back at a depth shallower than `page_depth`, i.e. until the
+/// `page` element that was open at that depth (however deeply nested the error was) has closed.
+/// Used to recover from an error encountered partway through a `page` element in lenient mode.
+fn resync(parser: &mut Parser<impl Read>, page_depth: u32) {
+    while parser.depth >= page_depth {
+        if read_event(parser).is_err() {
+            return;
+        }
+    }
+}
+
+fn parse_uint<T: std::str::FromStr>(
+    parser: &mut Parser<impl Read>,
+    output: &Option<T>
+) -> Result<T, Error> {
+    // `parse_text` accumulates `Whitespace` events along with `Characters`/`CData`, which a
+    // purely numeric element shouldn't have, but trim defensively rather than let surrounding
+    // whitespace turn into a spurious `Error::Format`.
+    match parse_text(parser, output)?.trim().parse() {
+        Err(_) => Err(Error::Format(parser.event_reader.position())),
+        Ok(value) => Ok(value)
     }
 }
 
+/// Reads the text content of an element, assuming its start tag has already been consumed.
+///
+/// Large elements (most importantly `text`) can have their content split by xml-rs across several
+/// `Characters`/`CData` events, possibly interleaved with `Whitespace` events; this accumulates all
+/// of them into a single `String` rather than assuming the content arrives in one event.
 fn parse_text(
-    event_reader: &mut EventReader<impl Read>,
+    parser: &mut Parser<impl Read>,
     output: &Option<impl Sized>
 ) -> Result<String, Error> {
     if output.is_some() {
-        return Err(Error::Format(event_reader.position()));
+        return Err(Error::Format(parser.event_reader.position()));
     }
-    match event_reader.next()? {
-        XmlEvent::Characters(characters) => if let XmlEvent::EndElement { .. } = event_reader.next()? {
-            return Ok(characters);
-        },
-        XmlEvent::EndElement { .. } => return Ok(String::new()),
-        _ => {}
+    let mut text = String::new();
+    loop {
+        match read_event(parser)? {
+            XmlEvent::CData(characters) | XmlEvent::Characters(characters) | XmlEvent::Whitespace(characters) => text.push_str(&characters),
+            XmlEvent::EndElement { .. } => return Ok(text),
+            XmlEvent::StartElement { .. } => return Err(Error::Format(parser.event_reader.position())),
+            _ => {}
+        }
     }
-    Err(Error::Format(event_reader.position()))
 }
 
-fn skip_element(event_reader: &mut EventReader<impl Read>) {
+fn skip_element(parser: &mut Parser<impl Read>) {
     let mut level = 0;
-    while let Ok(event) = event_reader.next() {
+    while let Ok(event) = read_event(parser) {
         match event {
             XmlEvent::EndElement { .. } => {
                 if level == 0 {
@@ -297,3 +677,201 @@ fn skip_element(event_reader: &mut EventReader<impl Read>) {
         }
     }
 }
+
+/// A rough, best-effort conversion of wiki text to plain text.
+///
+/// This is not a wiki text parser; see the module's Caution section. It drops HTML comments and
+/// `ref` tags, drops templates (`{{...}}`) and tables (`{|...|}`) including any nested ones,
+/// replaces `[[target|display]]`/`[[target]]` links with their display text, and removes
+/// `'''`/`''` emphasis markers.
+#[cfg(feature = "plain_text")]
+fn strip_wikitext(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while !rest.is_empty() {
+        if rest.starts_with("<!--") {
+            rest = match rest.find("-->") {
+                Some(end) => &rest[end + "-->".len()..],
+                None => ""
+            };
+            continue;
+        }
+        if rest.starts_with("<ref") {
+            rest = match rest.find('>') {
+                // Self-closing, e.g. `<ref name="x"/>` or `<references/>`: there is no
+                // matching `</ref>` to search for, so just drop the tag itself.
+                Some(tag_end) if rest[..tag_end].ends_with('/') => &rest[tag_end + 1..],
+                _ => match rest.find("</ref>") {
+                    Some(end) => &rest[end + "</ref>".len()..],
+                    None => ""
+                }
+            };
+            continue;
+        }
+        if rest.starts_with("{{") {
+            rest = &rest[skip_balanced_wikitext(rest, "{{", "}}").unwrap_or(rest.len())..];
+            continue;
+        }
+        if rest.starts_with("{|") {
+            rest = &rest[skip_table(rest).unwrap_or(rest.len())..];
+            continue;
+        }
+        if rest.starts_with("[[") {
+            match skip_balanced_wikitext(rest, "[[", "]]") {
+                Some(end) => {
+                    let inner = &rest[2..end - 2];
+                    let display = match inner.rfind('|') {
+                        Some(index) => &inner[index + 1..],
+                        None => inner
+                    };
+                    output.push_str(&strip_wikitext(display));
+                    rest = &rest[end..];
+                }
+                None => {
+                    // Unclosed link: there is no `]]` to strip off, so keep the rest of the
+                    // text (minus the opening `[[`) rather than truncating it.
+                    output.push_str(&strip_wikitext(&rest[2..]));
+                    rest = "";
+                }
+            }
+            continue;
+        }
+        if rest.starts_with("'''") {
+            rest = &rest[3..];
+            continue;
+        }
+        if rest.starts_with("''") {
+            rest = &rest[2..];
+            continue;
+        }
+        let next = rest.chars().next().expect("rest is not empty");
+        output.push(next);
+        rest = &rest[next.len_utf8()..];
+    }
+    output
+}
+
+/// Finds the end of the bracketed construct starting at the beginning of `text`, handling nested
+/// occurrences of the same `open`/`close` pair. Returns `None` if it is never closed.
+#[cfg(feature = "plain_text")]
+fn skip_balanced_wikitext(text: &str, open: &str, close: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut index = 0;
+    while index < text.len() {
+        if text[index..].starts_with(open) {
+            depth += 1;
+            index += open.len();
+        } else if text[index..].starts_with(close) {
+            depth -= 1;
+            index += close.len();
+            if depth == 0 {
+                return Some(index);
+            }
+        } else {
+            let next = text[index..].chars().next().expect("index is a char boundary");
+            index += next.len_utf8();
+        }
+    }
+    None
+}
+
+/// Finds the end of the `{| ... |}` table starting at the beginning of `text`, handling nested
+/// tables. Returns `None` if it is never closed.
+///
+/// Unlike `skip_balanced_wikitext(text, "{|", "|}")`, this also skips any `{{ ... }}` template
+/// nested directly in the table as an opaque unit, so a `|}` that is really part of the
+/// template's own closing `}}` (as in `{{x|}}`) is never mistaken for the table's close.
+#[cfg(feature = "plain_text")]
+fn skip_table(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut index = 0;
+    while index < text.len() {
+        if text[index..].starts_with("{{") {
+            index += skip_balanced_wikitext(&text[index..], "{{", "}}")?;
+        } else if text[index..].starts_with("{|") {
+            depth += 1;
+            index += "{|".len();
+        } else if text[index..].starts_with("|}") {
+            depth -= 1;
+            index += "|}".len();
+            if depth == 0 {
+                return Some(index);
+            }
+        } else {
+            let next = text[index..].chars().next().expect("index is a char boundary");
+            index += next.len_utf8();
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, ParserBuilder};
+
+    fn dump(pages: &str) -> String {
+        format!(r#"<mediawiki xmlns="http://www.mediawiki.org/xml/export-0.10/">{}</mediawiki>"#, pages)
+    }
+
+    #[test]
+    fn parse_text_accumulates_cdata_and_characters() {
+        let source = dump(
+            "<page><title>T</title><ns>0</ns><revision>\
+                <text><![CDATA[Hello ]]>World</text>\
+            </revision></page>"
+        );
+        let page = parse(source.as_bytes()).next().unwrap().unwrap();
+        assert_eq!(page.revision().unwrap().text, "Hello World");
+    }
+
+    #[test]
+    fn lenient_resyncs_past_a_page_malformed_deep_inside_a_revision() {
+        let source = dump(
+            "<page><title>Bad</title><ns>0</ns><revision>\
+                <comment>a</comment><comment>b</comment><text>x</text>\
+            </revision></page>\
+            <page><title>Good</title><ns>0</ns><revision><text>ok</text></revision></page>"
+        );
+        let mut parser = ParserBuilder::new().lenient(true).build(source.as_bytes());
+        assert!(parser.next().unwrap().is_err());
+        let page = parser.next().unwrap().unwrap();
+        assert_eq!(page.title, "Good");
+        assert_eq!(page.revision().unwrap().text, "ok");
+        assert_eq!(parser.skipped_pages(), 1);
+        assert!(parser.next().is_none());
+    }
+
+    #[cfg(feature = "plain_text")]
+    #[test]
+    fn strip_wikitext_drops_templates_and_comments() {
+        assert_eq!(super::strip_wikitext("Hello {{cite|x=1}} World"), "Hello  World");
+        assert_eq!(super::strip_wikitext("A<!-- hidden -->B"), "AB");
+    }
+
+    #[cfg(feature = "plain_text")]
+    #[test]
+    fn strip_wikitext_renders_piped_links_as_display_text() {
+        assert_eq!(super::strip_wikitext("See [[Target|display text]] here"), "See display text here");
+        assert_eq!(super::strip_wikitext("[[Target]]"), "Target");
+    }
+
+    #[cfg(feature = "plain_text")]
+    #[test]
+    fn strip_wikitext_keeps_unclosed_link_text_instead_of_truncating() {
+        assert_eq!(super::strip_wikitext("abc [[open link without close"), "abc open link without close");
+    }
+
+    #[cfg(feature = "plain_text")]
+    #[test]
+    fn strip_wikitext_drops_self_closing_and_normal_ref_tags() {
+        assert_eq!(super::strip_wikitext("Start<ref name=\"x\"/>End"), "StartEnd");
+        assert_eq!(super::strip_wikitext("A<ref>foot</ref>B"), "AB");
+        assert_eq!(super::strip_wikitext("<references/>tail"), "tail");
+    }
+
+    #[cfg(feature = "plain_text")]
+    #[test]
+    fn strip_wikitext_table_close_is_not_confused_by_a_nested_templates_closing_braces() {
+        assert_eq!(super::strip_wikitext("{|{{x|}}|}Y"), "Y");
+    }
+}